@@ -2,14 +2,16 @@
 
 use std::{
     alloc::GlobalAlloc,
-    sync::atomic::{AtomicUsize, Ordering},
-    thread::sleep,
-    time::Duration,
+    sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering},
 };
 
 #[cfg(feature = "async_tokio")]
 use std::future::Future;
 
+#[cfg(feature = "backtrace")]
+use std::{backtrace::Backtrace, cell::RefCell};
+
+use atomic_wait::{wait, wake_one};
 use stats_alloc::{Stats, StatsAlloc};
 
 #[cfg(feature = "async_tokio")]
@@ -18,14 +20,85 @@ use tokio::{runtime, task::spawn_blocking};
 const STATE_UNLOCKED: usize = 0;
 const STATE_IN_USE: usize = 1;
 
-const SLEEP: Duration = Duration::from_micros(50);
+/// Thread id used to mark a [ThreadSlot] as free. `pthread_self` never returns this value.
+const SLOT_UNCLAIMED: usize = 0;
+
+/// Number of concurrent per-thread measurements [LockedAllocator] can track at once.
+const THREAD_SLOTS: usize = 64;
+
+#[cfg(feature = "backtrace")]
+thread_local! {
+    /// The [Backtrace] captured for the first violation observed by [assert_no_alloc] on this
+    /// thread, if any. Captured after the triggering allocation has already succeeded, since
+    /// capturing a backtrace itself needs a heap to work with.
+    static VIOLATION_BACKTRACE: RefCell<Option<Backtrace>> = const { RefCell::new(None) };
+}
+
+/// A single entry in [LockedAllocator]'s per-thread tracking table.
+///
+/// Slots are claimed by a thread id with `compare_exchange` so two threads can never share one,
+/// and all counters are plain atomic adds so recording stays allocation-free on the hot path.
+struct ThreadSlot {
+    thread_id: AtomicUsize,
+    allocations: AtomicUsize,
+    deallocations: AtomicUsize,
+    reallocations: AtomicUsize,
+    bytes_allocated: AtomicUsize,
+    bytes_deallocated: AtomicUsize,
+    bytes_reallocated: AtomicUsize,
+}
+
+impl ThreadSlot {
+    const fn new() -> Self {
+        Self {
+            thread_id: AtomicUsize::new(SLOT_UNCLAIMED),
+            allocations: AtomicUsize::new(0),
+            deallocations: AtomicUsize::new(0),
+            reallocations: AtomicUsize::new(0),
+            bytes_allocated: AtomicUsize::new(0),
+            bytes_deallocated: AtomicUsize::new(0),
+            bytes_reallocated: AtomicUsize::new(0),
+        }
+    }
+
+    fn stats(&self) -> Stats {
+        Stats {
+            allocations: self.allocations.load(Ordering::SeqCst),
+            deallocations: self.deallocations.load(Ordering::SeqCst),
+            reallocations: self.reallocations.load(Ordering::SeqCst),
+            bytes_allocated: self.bytes_allocated.load(Ordering::SeqCst),
+            bytes_deallocated: self.bytes_deallocated.load(Ordering::SeqCst),
+            bytes_reallocated: self.bytes_reallocated.load(Ordering::SeqCst) as isize,
+        }
+    }
+}
 
 pub struct LockedAllocator<T>
 where
     T: GlobalAlloc,
 {
+    /// Either [STATE_UNLOCKED], [STATE_IN_USE], or a `pthread_self` thread id claiming the full
+    /// lock. Holds the actual identity, so it stays `usize`-wide; [Self::futex] is the narrow word
+    /// threads actually park on, since `atomic_wait` only supports fixed-width atomics.
     locked: AtomicUsize,
+    /// A generation counter bumped by [Self::notify_waiters] every time [Self::locked] transitions
+    /// back to [STATE_UNLOCKED]. Parked threads wait on this instead of parking directly on
+    /// [Self::locked], so a 64-bit thread id never has to be squeezed into the 32 bits `wait`/
+    /// `wake_one` support. Callers must snapshot this *before* attempting the CAS on [Self::locked]
+    /// and pass that snapshot to `wait`: if a release races the CAS and bumps the generation first,
+    /// the snapshot is already stale, so `wait` sees it doesn't match the current value and returns
+    /// immediately instead of parking on an unlock that already happened.
+    futex: AtomicU32,
     inner: StatsAlloc<T>,
+    thread_slots: [ThreadSlot; THREAD_SLOTS],
+    current_live_bytes: AtomicUsize,
+    peak_live_bytes: AtomicUsize,
+    violations: AtomicUsize,
+    /// Whether the thread currently holding [Self::locked] is inside [assert_no_alloc], as
+    /// opposed to a plain [memory_measured]/[memory_measured_future] region. Gates
+    /// [Self::record_violation] so ordinary measurements are never treated as violations, and so
+    /// their `Stats`/peak bytes can't be perturbed by the `backtrace` feature's capture.
+    asserting: AtomicBool,
 }
 
 impl<T> LockedAllocator<T>
@@ -34,7 +107,18 @@ where
 {
     pub const fn new(inner: StatsAlloc<T>) -> Self {
         let locked = AtomicUsize::new(0);
-        Self { locked, inner }
+        const EMPTY_SLOT: ThreadSlot = ThreadSlot::new();
+        let thread_slots = [EMPTY_SLOT; THREAD_SLOTS];
+        Self {
+            locked,
+            futex: AtomicU32::new(0),
+            inner,
+            thread_slots,
+            current_live_bytes: AtomicUsize::new(0),
+            peak_live_bytes: AtomicUsize::new(0),
+            violations: AtomicUsize::new(0),
+            asserting: AtomicBool::new(false),
+        }
     }
 
     /// An allocation free way to get the current thread id.
@@ -44,11 +128,17 @@ where
 
     /// An allocation free serialization code that runs prior to any allocator operation.
     /// Returns whether the current thread locked the allocator.
+    ///
+    /// Blocks via a futex wait on [Self::futex] instead of polling, so a contended thread costs
+    /// no CPU and wakes as soon as the lock is released. The generation is snapshotted before the
+    /// CAS attempt, not after it fails, so a release racing the CAS can't be missed.
     fn before_op(&self) -> bool {
         let current_thread_id = Self::current_thread_id();
 
         loop {
-            match self.locked.compare_exchange(
+            let generation = self.futex.load(Ordering::SeqCst);
+
+            match self.locked.compare_exchange_weak(
                 STATE_UNLOCKED,
                 STATE_IN_USE,
                 Ordering::SeqCst,
@@ -59,10 +149,13 @@ where
                     if existing == current_thread_id {
                         return true;
                     }
+
+                    // A weak CAS may fail spuriously while the lock is free: retry, don't park.
+                    if existing != STATE_UNLOCKED {
+                        wait(&self.futex, generation);
+                    }
                 }
             }
-
-            sleep(SLEEP);
         }
 
         false
@@ -73,24 +166,40 @@ where
         let current_thread_id = Self::current_thread_id();
 
         loop {
-            match self.locked.compare_exchange(
+            let generation = self.futex.load(Ordering::SeqCst);
+
+            match self.locked.compare_exchange_weak(
                 STATE_IN_USE,
                 STATE_UNLOCKED,
                 Ordering::SeqCst,
                 Ordering::SeqCst,
             ) {
-                Ok(_) => break,
+                Ok(_) => {
+                    self.notify_waiters();
+                    break;
+                }
                 Err(existing) => {
                     if existing == current_thread_id {
                         break;
                     }
+
+                    // A weak CAS may fail spuriously while this thread still holds
+                    // [STATE_IN_USE]: retry, don't park.
+                    if existing != STATE_IN_USE {
+                        wait(&self.futex, generation);
+                    }
                 }
             }
-
-            sleep(SLEEP);
         }
     }
 
+    /// Bumps [Self::futex] and wakes one parked waiter. Called whenever [Self::locked] transitions
+    /// back to [STATE_UNLOCKED], from [Self::after_op] and [Self::unlock].
+    fn notify_waiters(&self) {
+        self.futex.fetch_add(1, Ordering::SeqCst);
+        wake_one(&self.futex);
+    }
+
     /// A serialization wrapper to use for all allocator operations.
     fn serialized<F, O>(&self, op: F) -> O
     where
@@ -108,19 +217,23 @@ where
         let current_thread_id = Self::current_thread_id();
 
         loop {
-            let r = self.locked.compare_exchange(
+            let generation = self.futex.load(Ordering::SeqCst);
+
+            match self.locked.compare_exchange_weak(
                 STATE_UNLOCKED,
                 current_thread_id,
                 Ordering::SeqCst,
                 Ordering::SeqCst,
-            );
-
-            if r.is_ok() {
-                break;
+            ) {
+                Ok(_) => break,
+                // Spurious weak-CAS failure while the lock is free: retry, don't park.
+                Err(STATE_UNLOCKED) => {}
+                Err(_) => wait(&self.futex, generation),
             }
-
-            sleep(SLEEP);
         }
+
+        self.current_live_bytes.store(0, Ordering::SeqCst);
+        self.peak_live_bytes.store(0, Ordering::SeqCst);
     }
 
     /// Unlocks the allocator to allow operations from any thread.
@@ -133,12 +246,142 @@ where
                 .compare_exchange(expected, STATE_UNLOCKED, Ordering::SeqCst, Ordering::SeqCst)
                 .unwrap()
         );
+
+        self.notify_waiters();
     }
 
     /// Returns [Stats] from the wrapped [StatsAlloc].
     fn stats(&self) -> Stats {
         self.inner.stats()
     }
+
+    /// Returns the high-water mark of [Self::current_live_bytes] reached since the last [Self::lock].
+    fn peak_bytes(&self) -> usize {
+        self.peak_live_bytes.load(Ordering::SeqCst)
+    }
+
+    /// Accounts for a live-byte change, growing or shrinking, and bumps the peak if it was exceeded.
+    ///
+    /// `current_live_bytes` only starts counting from [Self::lock], so freeing memory that was
+    /// allocated before the measured region would otherwise underflow it to a huge `usize` (and
+    /// latch that garbage onto `peak_live_bytes` via `fetch_max`). Clamp the shrink to zero instead
+    /// of wrapping; such a free can only bring the count down to zero, not below it.
+    fn track_live_bytes(&self, delta: isize) {
+        if delta >= 0 {
+            let current = self.current_live_bytes.fetch_add(delta as usize, Ordering::SeqCst)
+                + delta as usize;
+            self.peak_live_bytes.fetch_max(current, Ordering::SeqCst);
+        } else {
+            let shrink = (-delta) as usize;
+
+            let _ = self
+                .current_live_bytes
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |current| {
+                    Some(current.saturating_sub(shrink))
+                });
+        }
+    }
+
+    /// Resets the violation counter used by [assert_no_alloc], for the start of a new region.
+    fn reset_violations(&self) {
+        self.violations.store(0, Ordering::SeqCst);
+    }
+
+    /// Returns the number of locked allocator operations observed since the last
+    /// [Self::reset_violations], i.e. the `f` passed to [assert_no_alloc] allocating.
+    fn violations(&self) -> usize {
+        self.violations.load(Ordering::SeqCst)
+    }
+
+    /// Marks the region currently held by [Self::lock] as an [assert_no_alloc] call, so
+    /// [Self::record_violation] knows to actually record what it sees.
+    fn begin_assert(&self) {
+        self.asserting.store(true, Ordering::SeqCst);
+    }
+
+    /// Clears the marker set by [Self::begin_assert].
+    fn end_assert(&self) {
+        self.asserting.store(false, Ordering::SeqCst);
+    }
+
+    /// Whether the thread holding [Self::lock] is inside [assert_no_alloc].
+    fn is_asserting(&self) -> bool {
+        self.asserting.load(Ordering::SeqCst)
+    }
+
+    /// Records an allocator operation made by the thread holding the lock, for
+    /// [assert_no_alloc]. Panicking here would be unsound since the unwinder itself allocates, so
+    /// this only bumps an atomic counter; with the `backtrace` feature, the first violation also
+    /// captures a [Backtrace], which only works because the triggering allocation already
+    /// succeeded by the time this runs.
+    ///
+    /// Callers must check [Self::is_asserting] first: a plain [memory_measured]/
+    /// [memory_measured_future] region also goes through the same locked code path, and recording
+    /// a violation there — especially capturing a backtrace, which itself allocates — would
+    /// corrupt the `Stats`/peak bytes being measured.
+    fn record_violation(&self) {
+        let is_first = self.violations.fetch_add(1, Ordering::SeqCst) == 0;
+
+        #[cfg(feature = "backtrace")]
+        if is_first {
+            VIOLATION_BACKTRACE.with(|backtrace| {
+                *backtrace.borrow_mut() = Some(Backtrace::capture());
+            });
+        }
+
+        #[cfg(not(feature = "backtrace"))]
+        let _ = is_first;
+    }
+
+    /// Claims a free [ThreadSlot] for the current thread, zeroing its counters.
+    ///
+    /// Returns `None` if all [THREAD_SLOTS] are currently claimed, including by this thread.
+    fn claim_thread_slot(&self) -> Option<usize> {
+        let current_thread_id = Self::current_thread_id();
+
+        for (index, slot) in self.thread_slots.iter().enumerate() {
+            if slot
+                .thread_id
+                .compare_exchange(
+                    SLOT_UNCLAIMED,
+                    current_thread_id,
+                    Ordering::SeqCst,
+                    Ordering::SeqCst,
+                )
+                .is_ok()
+            {
+                slot.allocations.store(0, Ordering::SeqCst);
+                slot.deallocations.store(0, Ordering::SeqCst);
+                slot.reallocations.store(0, Ordering::SeqCst);
+                slot.bytes_allocated.store(0, Ordering::SeqCst);
+                slot.bytes_deallocated.store(0, Ordering::SeqCst);
+                slot.bytes_reallocated.store(0, Ordering::SeqCst);
+
+                return Some(index);
+            }
+        }
+
+        None
+    }
+
+    /// Releases a [ThreadSlot] previously returned by [Self::claim_thread_slot].
+    fn release_thread_slot(&self, index: usize) {
+        self.thread_slots[index]
+            .thread_id
+            .store(SLOT_UNCLAIMED, Ordering::SeqCst);
+    }
+
+    /// An allocation free way to record an allocator operation against the current thread's slot,
+    /// if it has one claimed. Does nothing for threads without a claimed slot.
+    fn record_thread_local(&self, current_thread_id: usize, record: impl FnOnce(&ThreadSlot)) {
+        if let Some(slot) = self
+            .thread_slots
+            .iter()
+            .find(|slot| slot.thread_id.load(Ordering::SeqCst) == current_thread_id)
+        {
+            record(slot);
+        }
+    }
 }
 
 unsafe impl<T> GlobalAlloc for LockedAllocator<T>
@@ -146,61 +389,199 @@ where
     T: GlobalAlloc,
 {
     unsafe fn alloc(&self, layout: std::alloc::Layout) -> *mut u8 {
+        self.record_thread_local(Self::current_thread_id(), |slot| {
+            slot.allocations.fetch_add(1, Ordering::SeqCst);
+            slot.bytes_allocated.fetch_add(layout.size(), Ordering::SeqCst);
+        });
+
         self.serialized(|is_locked| {
+            self.track_live_bytes(layout.size() as isize);
+
+            let ptr = self.inner.alloc(layout);
+
             if is_locked {
                 probe::probe!(LockedAllocator, alloc_locked);
+
+                if self.is_asserting() {
+                    self.record_violation();
+                }
             }
 
-            self.inner.alloc(layout)
+            ptr
         })
     }
 
     unsafe fn dealloc(&self, ptr: *mut u8, layout: std::alloc::Layout) {
+        self.record_thread_local(Self::current_thread_id(), |slot| {
+            slot.deallocations.fetch_add(1, Ordering::SeqCst);
+            slot.bytes_deallocated.fetch_add(layout.size(), Ordering::SeqCst);
+        });
+
         self.serialized(|is_locked| {
+            self.track_live_bytes(-(layout.size() as isize));
+
+            self.inner.dealloc(ptr, layout);
+
             if is_locked {
                 probe::probe!(LockedAllocator, dealloc_locked);
-            }
 
-            self.inner.dealloc(ptr, layout)
+                if self.is_asserting() {
+                    self.record_violation();
+                }
+            }
         })
     }
 
     unsafe fn realloc(&self, ptr: *mut u8, layout: std::alloc::Layout, new_size: usize) -> *mut u8 {
+        self.record_thread_local(Self::current_thread_id(), |slot| {
+            slot.reallocations.fetch_add(1, Ordering::SeqCst);
+
+            let old_size = layout.size();
+
+            match new_size.cmp(&old_size) {
+                std::cmp::Ordering::Greater => {
+                    slot.bytes_allocated
+                        .fetch_add(new_size - old_size, Ordering::SeqCst);
+                }
+                std::cmp::Ordering::Less => {
+                    slot.bytes_deallocated
+                        .fetch_add(old_size - new_size, Ordering::SeqCst);
+                }
+                std::cmp::Ordering::Equal => {}
+            }
+
+            // Wrapping, so that [ThreadSlot::stats] reads the sum back as a signed net change.
+            slot.bytes_reallocated
+                .fetch_add(new_size.wrapping_sub(old_size), Ordering::SeqCst);
+        });
+
         self.serialized(|is_locked| {
+            self.track_live_bytes(new_size as isize - layout.size() as isize);
+
+            let ptr = self.inner.realloc(ptr, layout, new_size);
+
             if is_locked {
                 probe::probe!(LockedAllocator, realloc_locked);
+
+                if self.is_asserting() {
+                    self.record_violation();
+                }
             }
 
-            self.inner.realloc(ptr, layout, new_size)
+            ptr
         })
     }
 }
 
-/// Measure memory and return [Stats] object for the runtime of the passed closure.
-pub fn memory_measured<A, F>(alloc: &LockedAllocator<A>, f: F) -> Stats
+/// The result of a locked measurement: cumulative [Stats] alongside the high-water mark of live
+/// bytes reached at any point during the measured region, which a net delta alone cannot show
+/// for memory that was allocated and freed again before the closure returned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Measurement {
+    pub stats: Stats,
+    pub peak_bytes: usize,
+}
+
+/// Measure memory for the runtime of the passed closure, returning a [Measurement] alongside
+/// whatever `f` returns.
+pub fn memory_measured<A, F, R>(alloc: &LockedAllocator<A>, f: F) -> (Measurement, R)
 where
     A: GlobalAlloc,
-    F: FnOnce(),
+    F: FnOnce() -> R,
 {
     alloc.lock();
 
     let before = alloc.stats();
 
-    f();
+    let result = f();
 
     let after = alloc.stats();
+    let peak_bytes = alloc.peak_bytes();
 
     alloc.unlock();
 
-    after - before
+    (
+        Measurement {
+            stats: after - before,
+            peak_bytes,
+        },
+        result,
+    )
+}
+
+/// Measure memory for the runtime of the passed closure using only the measuring thread's
+/// allocations, without serializing the rest of the process behind a lock.
+///
+/// Unlike [memory_measured], other threads keep allocating freely while `f` runs: only
+/// allocator operations observed on the current thread are counted, via a [ThreadSlot] claimed
+/// for the duration of the call. Returns `None` if no slot is free, i.e. [THREAD_SLOTS]
+/// concurrent per-thread measurements are already in flight.
+pub fn memory_measured_per_thread<A, F>(alloc: &LockedAllocator<A>, f: F) -> Option<Stats>
+where
+    A: GlobalAlloc,
+    F: FnOnce(),
+{
+    let index = alloc.claim_thread_slot()?;
+
+    f();
+
+    let stats = alloc.thread_slots[index].stats();
+
+    alloc.release_thread_slot(index);
+
+    Some(stats)
 }
 
-/// Measure memory and return [Stats] object for the runtime of the passed future.
+/// Runs `f` with `alloc` locked to the current thread, then panics if any allocator operation
+/// happened while it ran.
+///
+/// Useful for asserting that performance-critical code performs zero heap activity. Locking works
+/// the same way as [memory_measured], but panicking from inside `GlobalAlloc` itself would be
+/// unsound (the unwinder allocates), so violations are just counted atomically while locked and
+/// only turned into a panic once `f` has returned and the allocator is unlocked again. With the
+/// `backtrace` feature enabled, the first violation's backtrace is captured and included in the
+/// panic message.
+pub fn assert_no_alloc<A, F>(alloc: &LockedAllocator<A>, f: F)
+where
+    A: GlobalAlloc,
+    F: FnOnce(),
+{
+    alloc.lock();
+    alloc.reset_violations();
+    alloc.begin_assert();
+
+    f();
+
+    alloc.end_assert();
+
+    let violations = alloc.violations();
+
+    alloc.unlock();
+
+    #[cfg(feature = "backtrace")]
+    {
+        let backtrace = VIOLATION_BACKTRACE.with(|backtrace| backtrace.borrow_mut().take());
+        assert_eq!(
+            violations, 0,
+            "assert_no_alloc: allocator operation observed, first at:\n{backtrace:#?}"
+        );
+    }
+
+    #[cfg(not(feature = "backtrace"))]
+    assert_eq!(violations, 0, "assert_no_alloc: allocator operation observed");
+}
+
+/// Measure memory for the runtime of the passed future, returning a [Measurement] alongside
+/// whatever `f` resolves to.
 #[cfg(feature = "async_tokio")]
-pub async fn memory_measured_future<A, F>(alloc: &'static LockedAllocator<A>, f: F) -> Stats
+pub async fn memory_measured_future<A, F, R>(
+    alloc: &'static LockedAllocator<A>,
+    f: F,
+) -> (Measurement, R)
 where
     A: GlobalAlloc + Send + Sync,
-    F: Future<Output = ()> + Send + 'static,
+    F: Future<Output = R> + Send + 'static,
+    R: Send + 'static,
 {
     // Tokio runtime cannot be created from a thread that is a part of a runtime already.
     spawn_blocking(|| {
@@ -214,13 +595,20 @@ where
 
             let before = alloc.stats();
 
-            f.await;
+            let result = f.await;
 
             let after = alloc.stats();
+            let peak_bytes = alloc.peak_bytes();
 
             alloc.unlock();
 
-            after - before
+            (
+                Measurement {
+                    stats: after - before,
+                    peak_bytes,
+                },
+                result,
+            )
         })
     })
     .await
@@ -246,18 +634,16 @@ mod tests {
 
     #[test]
     fn it_works() {
-        let mut length = 0;
-
-        let stats = memory_measured(&GLOBAL, || {
+        let (measurement, length) = memory_measured(&GLOBAL, || {
             let s = "whoa".to_owned().replace("whoa", "wow").to_owned();
 
-            length = s.len();
+            s.len()
         });
 
         assert_eq!(length, 3);
 
         assert_eq!(
-            stats,
+            measurement.stats,
             Stats {
                 allocations: 3,
                 deallocations: 3,
@@ -267,19 +653,20 @@ mod tests {
                 bytes_reallocated: 0
             }
         );
+        assert!(measurement.peak_bytes > 0 && measurement.peak_bytes <= measurement.stats.bytes_allocated);
 
-        let stats = memory_measured(&GLOBAL, || {
+        let (measurement, length) = memory_measured(&GLOBAL, || {
             let mut v = vec![1, 2, 3, 4, 5];
 
             v.push(6);
 
-            length = v.len();
+            v.len()
         });
 
         assert_eq!(length, 6);
 
         assert_eq!(
-            stats,
+            measurement.stats,
             Stats {
                 allocations: 1,
                 deallocations: 1,
@@ -289,6 +676,7 @@ mod tests {
                 bytes_reallocated: 20
             }
         );
+        assert!(measurement.peak_bytes > 0 && measurement.peak_bytes <= measurement.stats.bytes_allocated);
     }
 
     #[test]
@@ -306,21 +694,53 @@ mod tests {
             });
         }
 
-        let mut length = 0;
         let step = Duration::from_millis(150);
 
-        let stats = memory_measured(&GLOBAL, || {
+        let (measurement, length) = memory_measured(&GLOBAL, || {
             let s = "whoa".to_owned().replace("whoa", "wow").to_owned();
 
             sleep(step);
 
-            length = s.len();
+            s.len()
         });
 
         stop.store(true, Ordering::Relaxed);
 
         assert_eq!(length, 3);
 
+        assert_eq!(
+            measurement.stats,
+            Stats {
+                allocations: 3,
+                deallocations: 3,
+                reallocations: 0,
+                bytes_allocated: 15,
+                bytes_deallocated: 15,
+                bytes_reallocated: 0
+            }
+        );
+    }
+
+    #[test]
+    fn test_peak_bytes_ignores_pre_existing_frees() {
+        let ptr: *mut String = Box::into_raw(Box::new("already allocated".to_owned()));
+
+        let (measurement, _) = memory_measured(&GLOBAL, || {
+            unsafe { drop(Box::from_raw(ptr)) };
+        });
+
+        assert_eq!(measurement.peak_bytes, 0);
+    }
+
+    #[test]
+    fn test_memory_measured_per_thread() {
+        let stats = memory_measured_per_thread(&GLOBAL, || {
+            let s = "whoa".to_owned().replace("whoa", "wow").to_owned();
+
+            assert_eq!(s.len(), 3);
+        })
+        .expect("a thread slot should be free");
+
         assert_eq!(
             stats,
             Stats {
@@ -332,18 +752,71 @@ mod tests {
                 bytes_reallocated: 0
             }
         );
+
+        let stats = memory_measured_per_thread(&GLOBAL, || {
+            let mut v = vec![1, 2, 3, 4, 5];
+
+            v.push(6);
+        })
+        .expect("a thread slot should be free");
+
+        assert_eq!(
+            stats,
+            Stats {
+                allocations: 1,
+                deallocations: 1,
+                reallocations: 1,
+                bytes_allocated: 40,
+                bytes_deallocated: 40,
+                bytes_reallocated: 20
+            }
+        );
+    }
+
+    #[test]
+    fn test_memory_measured_per_thread_slot_exhaustion() {
+        let claimed: Vec<usize> = (0..THREAD_SLOTS)
+            .map(|_| GLOBAL.claim_thread_slot().expect("a thread slot should be free"))
+            .collect();
+
+        assert!(memory_measured_per_thread(&GLOBAL, || {}).is_none());
+
+        for index in claimed {
+            GLOBAL.release_thread_slot(index);
+        }
+
+        assert!(memory_measured_per_thread(&GLOBAL, || {}).is_some());
+    }
+
+    #[test]
+    fn test_assert_no_alloc() {
+        assert_no_alloc(&GLOBAL, || {
+            let _ = 1 + 1;
+        });
+
+        let result = std::panic::catch_unwind(|| {
+            assert_no_alloc(&GLOBAL, || {
+                let _ = "whoa".to_owned();
+            });
+        });
+
+        assert!(result.is_err());
     }
 
     #[tokio::test]
     #[cfg(feature = "async_tokio")]
     async fn test_tokio() {
-        let stats = memory_measured_future(&GLOBAL, async {
-            let _ = vec![1, 2, 3, 4];
+        let (measurement, length) = memory_measured_future(&GLOBAL, async {
+            let v = vec![1, 2, 3, 4];
+
+            v.len()
         })
         .await;
 
+        assert_eq!(length, 4);
+
         assert_eq!(
-            stats,
+            measurement.stats,
             Stats {
                 allocations: 1,
                 deallocations: 1,
@@ -353,5 +826,6 @@ mod tests {
                 bytes_reallocated: 0
             }
         );
+        assert_eq!(measurement.peak_bytes, 16);
     }
 }